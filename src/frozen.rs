@@ -0,0 +1,217 @@
+//! Append-only collections that push/insert through `&self`.
+//!
+//! Unlike [`VecOwner`](crate::VecOwner)/[`HashMapOwner`](crate::HashMapOwner), which split
+//! ownership from borrowing into an `Owner`/`Child` pair, the backing collection here lives
+//! behind an `UnsafeCell`, so a single shared `&self` is enough to both hold out earlier
+//! references and append new ones.
+//!
+//! # Example
+//! ```
+//! use push_and_read::FrozenVec;
+//! let vec = FrozenVec::new();
+//! let v1 = vec.push(Box::new(10));
+//! let v2 = vec.push(Box::new(20));
+//! assert_eq!(*v1, 10);
+//! assert_eq!(*v2, 20);
+//! ```
+
+use std::cell::UnsafeCell;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::{StableRef, StaticType};
+
+/// An append-only `Vec` that can be pushed to through a shared reference.
+///
+/// # Safety / soundness
+/// The returned reference points at the data `T` derefs to (guaranteed stable by
+/// [`StaticType`]), never at the `Vec` itself, so growing/reallocating the `Vec` never
+/// invalidates a reference handed out earlier.
+#[derive(Debug)]
+pub struct FrozenVec<T>(UnsafeCell<Vec<T>>);
+
+impl<T> FrozenVec<T>
+where T: StaticType {
+
+	/// Create a new empty `FrozenVec`.
+	pub fn new() -> Self {
+		Self(UnsafeCell::new(vec![]))
+	}
+
+	/// Pushes a value and returns a reference to it that stays valid for as long as
+	/// `self` lives.
+	///
+	/// ```
+	/// # use push_and_read::FrozenVec;
+	/// let vec = FrozenVec::new();
+	/// let v1 = vec.push(Box::new(10));
+	/// let v2 = vec.push(Box::new(20));
+	/// assert_eq!(*v1, 10);
+	/// assert_eq!(*v2, 20);
+	/// ```
+	pub fn push(&self, v: T) -> StableRef<'_, T::Ref> {
+		// compute the stable pointer before the value moves into the Vec, so we never
+		// rely on the Vec's (possibly reallocated) backing storage
+		let ptr = v.ref_ptr();
+
+		// Safety: `push` never hands out a `&mut Vec<T>`/`&[T]`, only pointers into
+		// `T`'s own stable allocation, so calling this again while an earlier reference
+		// is alive never aliases that reference. Re-entrant calls from `v`'s Drop/Deref
+		// are the only way to violate this, which `StaticType` implementors must avoid.
+		let vec = unsafe { &mut *self.0.get() };
+		vec.push(v);
+
+		debug_assert_eq!(
+			vec.last().unwrap().ref_ptr(),
+			ptr,
+			"Trait promises we're not uphold"
+		);
+
+		// Safety: ptr is valid for as long as `self` is, since T never moves once pushed
+		unsafe { StableRef::new(ptr) }
+	}
+
+	/// The number of elements pushed so far.
+	pub fn len(&self) -> usize {
+		unsafe { &*self.0.get() }.len()
+	}
+
+	/// Returns `true` if no elements have been pushed yet.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+}
+
+impl<T> Default for FrozenVec<T>
+where T: StaticType {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+
+/// An append-only `HashMap` that can be inserted into through a shared reference.
+///
+/// Same interior-mutability trick as [`FrozenVec`], with the same duplicate-key rejection
+/// `HashMapOwner`/`HashMapChild` already perform.
+#[derive(Debug)]
+pub struct FrozenMap<K, T>(UnsafeCell<HashMap<K, T>>);
+
+impl<K, T> FrozenMap<K, T>
+where
+	K: Hash + Eq + Clone,
+	T: StaticType {
+
+	/// Create a new empty `FrozenMap`.
+	pub fn new() -> Self {
+		Self(UnsafeCell::new(HashMap::new()))
+	}
+
+	/// Tries to insert `key`/`v`, returning `None` if the key already exists.
+	///
+	/// ```
+	/// # use push_and_read::FrozenMap;
+	/// let map = FrozenMap::new();
+	/// let v1 = map.try_insert("10", Box::new(10)).unwrap();
+	/// let v2 = map.try_insert("20", Box::new(20)).unwrap();
+	/// assert_eq!(*v1, 10);
+	/// assert_eq!(*v2, 20);
+	/// assert!(map.try_insert("10", Box::new(30)).is_none());
+	/// ```
+	pub fn try_insert(&self, key: K, v: T) -> Option<StableRef<'_, T::Ref>> {
+		// reject duplicate keys before committing, same as HashMapOwner::try_insert;
+		// this borrow ends here, before we take a `&mut` below
+		if unsafe { &*self.0.get() }.contains_key(&key) {
+			return None;
+		}
+
+		// compute the stable pointer before taking `&mut *self.0.get()`, same ordering
+		// as `FrozenVec::push`, so a re-entrant `try_insert`/`insert` call from `v`'s
+		// `ref_ptr()` never sees an aliasing `&mut` to the map
+		let ptr = v.ref_ptr();
+
+		// Safety: see `FrozenVec::push`; no reference into the HashMap itself is ever
+		// handed out, only pointers into each value's own stable allocation.
+		let map = unsafe { &mut *self.0.get() };
+
+		if cfg!(debug_assertions) {
+			map.insert(key.clone(), v);
+			let v = map.get(&key).unwrap();
+			assert_eq!(ptr, v.ref_ptr(), "Trait promises we're not uphold");
+		} else {
+			map.insert(key, v);
+		}
+
+		Some(unsafe { StableRef::new(ptr) })
+	}
+
+	/// Inserts `key`/`v`.
+	///
+	/// # Panics
+	/// Panics if `key` already exists.
+	///
+	/// ```
+	/// # use push_and_read::FrozenMap;
+	/// let map = FrozenMap::new();
+	/// let v1 = map.insert("10", Box::new(10));
+	/// let v2 = map.insert("20", Box::new(20));
+	/// assert_eq!(*v1, 10);
+	/// assert_eq!(*v2, 20);
+	/// ```
+	pub fn insert(&self, key: K, v: T) -> StableRef<'_, T::Ref> {
+		self.try_insert(key, v).expect("Key already exists")
+	}
+
+	/// The number of entries inserted so far.
+	pub fn len(&self) -> usize {
+		unsafe { &*self.0.get() }.len()
+	}
+
+	/// Returns `true` if no entries have been inserted yet.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+}
+
+impl<K, T> Default for FrozenMap<K, T>
+where
+	K: Hash + Eq + Clone,
+	T: StaticType {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+
+	use super::*;
+
+	#[test]
+	fn insert_to_frozen_vec() {
+		let v = FrozenVec::new();
+		let s1 = v.push(Box::new(String::from("hey")));
+		let s2 = v.push(Box::new(String::from("hey 2")));
+		assert_eq!("hey", s1.as_str());
+		assert_eq!("hey 2", s2.as_str());
+		assert_eq!(v.len(), 2);
+	}
+
+	#[test]
+	fn insert_twice_frozen_map() {
+		let files = FrozenMap::new();
+		files.insert("abc", vec![1].into_boxed_slice());
+		assert!(files.try_insert("abc", vec![1].into_boxed_slice()).is_none());
+	}
+
+	#[test]
+	fn push_while_holding_ref() {
+		let vec = FrozenVec::new();
+		let v1 = vec.push(Box::new(1));
+		let v2 = vec.push(Box::new(2));
+		// v1 is still valid even though we pushed again through the same `&self`
+		assert_eq!(*v1, 1);
+		assert_eq!(*v2, 2);
+	}
+}
@@ -27,10 +27,35 @@
 //! assert_eq!(*v1, 10);
 //! assert_eq!(*v2, 20);
 //! ```
+//!
+//! If you only ever append and never need a mutable handle to the owner,
+//! [`FrozenVec`]/[`FrozenMap`] offer the same guarantee through a plain `&self`, without the
+//! `Owner`/`Child` split. [`SyncVecOwner`] goes one step further and lets multiple threads push
+//! concurrently through a shared `Arc`.
+//!
+//! All of `push`/`insert` return a [`StableRef`], which derefs to `&T::Ref` like before but can
+//! also be narrowed to a subfield with [`StableRef::map`] while keeping the owner lifetime.
+//!
+//! `VecChild::try_push` and `HashMapChild::try_reserve` surface allocation failure as a
+//! `TryReserveError` instead of aborting, for callers that can't tolerate unwinding-on-OOM.
+//!
+//! `VecChild`/`HashMapChild` also expose `get`/`iter`, so an entry pushed earlier can be
+//! re-borrowed for the owner's lifetime instead of only the transient `&mut self` of `push`.
 
 
+use std::borrow::Borrow;
+use std::collections::{HashMap, TryReserveError};
 use std::hash::Hash;
-use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+
+mod frozen;
+mod stable_ref;
+mod sync;
+
+pub use frozen::{FrozenVec, FrozenMap};
+pub use stable_ref::StableRef;
+pub use sync::SyncVecOwner;
 
 #[derive(Debug)]
 pub struct VecOwner<T>(Vec<T>);
@@ -47,7 +72,19 @@ where T: StaticType {
 	/// VecOwner goes out of scope and that the ptr is only used to cast to a reference
 	#[inline]
 	pub(crate) fn push(&mut self, v: T) -> *const T::Ref {
+		self.try_push(v).expect("allocation failed")
+	}
+
+	/// Same as [`push`](Self::push), but surfaces an allocation failure instead of aborting.
+	///
+	/// you need to make sure that the pointer is not used after
+	/// VecOwner goes out of scope and that the ptr is only used to cast to a reference
+	#[inline]
+	pub(crate) fn try_push(&mut self, v: T) -> Result<*const T::Ref, TryReserveError> {
+		self.0.try_reserve(1)?;
+
 		let ptr = v.ref_ptr();
+		// won't reallocate, capacity was just reserved above
 		self.0.push(v);
 
 		debug_assert_eq!(
@@ -56,7 +93,7 @@ where T: StaticType {
 			"Trait promises we're not uphold"
 		);
 
-		ptr
+		Ok(ptr)
 	}
 
 	pub fn child(&mut self) -> VecChild<'_, T> {
@@ -84,10 +121,67 @@ where T: StaticType {
 	/// assert_eq!(*v1, 10);
 	/// assert_eq!(*v2, 20);
 	/// ```
-	pub fn push(&mut self, v: T) -> &'a T::Ref {
+	pub fn push(&mut self, v: T) -> StableRef<'a, T::Ref> {
 		let ptr = self.0.push(v);
 		// safe because ptr does not live longer than VecOwner
-		unsafe { &*ptr }
+		unsafe { StableRef::new(ptr) }
+	}
+
+	/// Same as [`push`](Self::push), but returns an error instead of aborting when the
+	/// allocator can't grow the backing `Vec`.
+	///
+	/// ```
+	/// # use push_and_read::{VecOwner, VecChild};
+	/// let mut vec = VecOwner::new();
+	/// let mut vec = vec.child();
+	/// let v1 = vec.try_push(Box::new(10)).unwrap();
+	/// assert_eq!(*v1, 10);
+	/// ```
+	pub fn try_push(&mut self, v: T) -> Result<StableRef<'a, T::Ref>, TryReserveError> {
+		let ptr = self.0.try_push(v)?;
+		// safe because ptr does not live longer than VecOwner
+		Ok(unsafe { StableRef::new(ptr) })
+	}
+
+	/// Returns a reference to the element at `index`, if any, carrying the owner's
+	/// lifetime rather than the lifetime of this borrow.
+	///
+	/// ```
+	/// # use push_and_read::{VecOwner, VecChild};
+	/// let mut vec = VecOwner::new();
+	/// let mut vec = vec.child();
+	/// vec.push(Box::new(10));
+	/// assert_eq!(*vec.get(0).unwrap(), 10);
+	/// assert!(vec.get(1).is_none());
+	/// ```
+	pub fn get(&self, index: usize) -> Option<StableRef<'a, T::Ref>> {
+		// Safety: `self.0` borrows the owner for `'a`, we only read through it here
+		let owner: &'a VecOwner<T> = unsafe { &*(&*self.0 as *const VecOwner<T>) };
+		let ptr = owner.0.get(index)?.ref_ptr();
+		Some(unsafe { StableRef::new(ptr) })
+	}
+
+	/// Iterates over every pushed element, in push order, yielding references that carry
+	/// the owner's lifetime rather than the lifetime of this borrow.
+	///
+	/// Collects eagerly rather than borrowing the backing `Vec`: each `StableRef` only
+	/// points into its own element's stable allocation (like `get`/`push` already do), so
+	/// holding the result across a later `push` stays sound even if the `Vec` reallocates.
+	///
+	/// ```
+	/// # use push_and_read::{VecOwner, VecChild};
+	/// let mut vec = VecOwner::new();
+	/// let mut vec = vec.child();
+	/// vec.push(Box::new(10));
+	/// vec.push(Box::new(20));
+	/// let sum: i32 = vec.iter().map(|v| *v).sum();
+	/// assert_eq!(sum, 30);
+	/// ```
+	pub fn iter(&self) -> std::vec::IntoIter<StableRef<'a, T::Ref>> {
+		let items: Vec<_> = self.0.0.iter()
+			.map(|v| unsafe { StableRef::new(v.ref_ptr()) })
+			.collect();
+		items.into_iter()
 	}
 }
 
@@ -106,14 +200,25 @@ where
 
 	/// you need to make sure that the pointer is not used after
 	/// VecOwner goes out of scope and that the ptr is only used to cast to a reference
+	///
+	/// `Ok(None)` means the key already existed, `Err` means the backing `HashMap`
+	/// couldn't grow to fit the new entry.
 	#[inline]
-	pub(crate) fn try_insert(&mut self, key: K, v: T) -> Option<*const T::Ref> {
+	pub(crate) fn try_insert(
+		&mut self,
+		key: K,
+		v: T,
+	) -> Result<Option<*const T::Ref>, TryReserveError> {
 		// check if key already contained in HashMap
 		// this needs to be done because else we would invalid the promise we give
 		if self.0.contains_key(&key) {
-			return None
+			return Ok(None)
 		}
 
+		// route capacity growth through try_reserve, same as VecOwner::try_push,
+		// so a caller that pre-reserved via HashMapChild::try_reserve never aborts here
+		self.0.try_reserve(1)?;
+
 		let ptr = v.ref_ptr();
 
 		// check that traits are upholding their promise
@@ -127,7 +232,13 @@ where
 			self.0.insert(key, v);
 		}
 
-		Some(ptr)
+		Ok(Some(ptr))
+	}
+
+	/// Reserves capacity for at least `additional` more entries, returning an error instead
+	/// of aborting if the allocator can't grow the backing `HashMap`.
+	pub(crate) fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+		self.0.try_reserve(additional)
 	}
 
 	pub fn child(&mut self) -> HashMapChild<'_, K, T> {
@@ -160,11 +271,12 @@ where
 	/// assert_eq!(*v1, 10);
 	/// assert_eq!(*v2, 20);
 	/// ```
-	pub fn try_insert(&mut self, key: K, v: T) -> Option<&'a T::Ref> {
+	pub fn try_insert(&mut self, key: K, v: T) -> Option<StableRef<'a, T::Ref>> {
 		self.0.try_insert(key, v)
+			.expect("allocation failed")
 			.map(|ptr| {
 				// safe because ref does not live longer than HashMapOwner
-				unsafe { &*ptr }
+				unsafe { StableRef::new(ptr) }
 			})
 	}
 
@@ -185,10 +297,72 @@ where
 	/// assert_eq!(*v1, 10);
 	/// assert_eq!(*v2, 20);
 	/// ```
-	pub fn insert(&mut self, key: K, v: T) -> &'a T::Ref {
-		let ptr = self.0.try_insert(key, v).expect("Key already exists");
+	pub fn insert(&mut self, key: K, v: T) -> StableRef<'a, T::Ref> {
+		let ptr = self.0.try_insert(key, v)
+			.expect("allocation failed")
+			.expect("Key already exists");
 		// safe because ref does not live longer than HashMapOwner
-		unsafe { &*ptr }
+		unsafe { StableRef::new(ptr) }
+	}
+
+	/// Reserves capacity for at least `additional` more entries, returning an error instead
+	/// of aborting if the allocator can't grow the backing `HashMap`.
+	///
+	/// ```
+	/// # use push_and_read::{HashMapOwner, HashMapChild};
+	/// let mut map = HashMapOwner::new();
+	/// let mut map = map.child();
+	/// map.try_reserve(4).unwrap();
+	/// map.insert("10", Box::new(10));
+	/// ```
+	pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+		self.0.try_reserve(additional)
+	}
+
+	/// Returns a reference to the value associated with `key`, if any, carrying the owner's
+	/// lifetime rather than the lifetime of this borrow.
+	///
+	/// ```
+	/// # use push_and_read::{HashMapOwner, HashMapChild};
+	/// let mut map = HashMapOwner::new();
+	/// let mut map = map.child();
+	/// map.insert("10", Box::new(10));
+	/// assert_eq!(*map.get("10").unwrap(), 10);
+	/// assert!(map.get("20").is_none());
+	/// ```
+	pub fn get<Q>(&self, key: &Q) -> Option<StableRef<'a, T::Ref>>
+	where
+		K: Borrow<Q>,
+		Q: Hash + Eq + ?Sized {
+		// Safety: `self.0` borrows the owner for `'a`, we only read through it here
+		let owner: &'a HashMapOwner<K, T> =
+			unsafe { &*(&*self.0 as *const HashMapOwner<K, T>) };
+		let ptr = owner.0.get(key)?.ref_ptr();
+		Some(unsafe { StableRef::new(ptr) })
+	}
+
+	/// Iterates over every inserted value, yielding references that carry the owner's
+	/// lifetime rather than the lifetime of this borrow.
+	///
+	/// Collects eagerly rather than borrowing the backing `HashMap`: each `StableRef` only
+	/// points into its own value's stable allocation (like `get`/`insert` already do), so
+	/// holding the result across a later `insert` stays sound even if the `HashMap`
+	/// reallocates.
+	///
+	/// ```
+	/// # use push_and_read::{HashMapOwner, HashMapChild};
+	/// let mut map = HashMapOwner::new();
+	/// let mut map = map.child();
+	/// map.insert("10", Box::new(10));
+	/// map.insert("20", Box::new(20));
+	/// let sum: i32 = map.iter().map(|v| *v).sum();
+	/// assert_eq!(sum, 30);
+	/// ```
+	pub fn iter(&self) -> std::vec::IntoIter<StableRef<'a, T::Ref>> {
+		let items: Vec<_> = self.0.0.values()
+			.map(|v| unsafe { StableRef::new(v.ref_ptr()) })
+			.collect();
+		items.into_iter()
 	}
 
 }
@@ -199,7 +373,11 @@ where
 /// If you implement this
 /// you need to guarantee that Self and Ref does not move in memory
 ///
-/// Most probably it should be allocated on the heap
+/// Most probably it should be allocated on the heap.
+///
+/// Implemented for `Box<T>`, `Rc<T>`, `Arc<T>`, `String` and `Vec<T>`, mirroring the
+/// `StableDeref` set: all of these deref to a heap allocation whose address is unaffected by
+/// moving the smart-pointer header around.
 pub unsafe trait StaticType {
 	type Ref: ?Sized;
 
@@ -209,19 +387,25 @@ pub unsafe trait StaticType {
 	fn ref_ptr(&self) -> *const Self::Ref;
 }
 
-// unsafe impl<T> StaticType for Vec<T> {
-// 	type Ref = [T];
+unsafe impl<T: ?Sized> StaticType for Box<T> {
+	type Ref = T;
 
-// 	fn ref_ptr(&self) -> *const Self::Ref {
-// 		&*self
-// 	}
+	#[inline]
+	fn ref_ptr(&self) -> *const Self::Ref {
+		&**self
+	}
+}
 
-// 	fn ref(&self) -> &Self::Ref {
-// 		&self
-// 	}
-// }
+unsafe impl<T> StaticType for Rc<T> {
+	type Ref = T;
 
-unsafe impl<T: ?Sized> StaticType for Box<T> {
+	#[inline]
+	fn ref_ptr(&self) -> *const Self::Ref {
+		&**self
+	}
+}
+
+unsafe impl<T> StaticType for Arc<T> {
 	type Ref = T;
 
 	#[inline]
@@ -230,6 +414,24 @@ unsafe impl<T: ?Sized> StaticType for Box<T> {
 	}
 }
 
+unsafe impl StaticType for String {
+	type Ref = str;
+
+	#[inline]
+	fn ref_ptr(&self) -> *const Self::Ref {
+		&self[..]
+	}
+}
+
+unsafe impl<T> StaticType for Vec<T> {
+	type Ref = [T];
+
+	#[inline]
+	fn ref_ptr(&self) -> *const Self::Ref {
+		&self[..]
+	}
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -245,8 +447,93 @@ mod tests {
 		let mut v = v.child();
 		let s1 = v.push(Box::new(String::from("hey")));
 		let s2 = v.push(Box::new(String::from("hey 2")));
-		assert_eq!("hey", s1);
-		assert_eq!("hey 2", s2);
+		assert_eq!("hey", s1.as_str());
+		assert_eq!("hey 2", s2.as_str());
+	}
+
+	#[test]
+	fn try_push_and_try_reserve() {
+		let mut v = VecOwner::new();
+		let mut v = v.child();
+		let v1 = v.try_push(Box::new(10)).unwrap();
+		assert_eq!(*v1, 10);
+
+		let mut map = HashMapOwner::new();
+		let mut map = map.child();
+		map.try_reserve(4).unwrap();
+		let v2 = map.insert("10", Box::new(10));
+		assert_eq!(*v2, 10);
+	}
+
+	#[test]
+	fn get_and_iter_vec() {
+		let mut v = VecOwner::new();
+		let mut v = v.child();
+		v.push(Box::new(10));
+		v.push(Box::new(20));
+
+		assert_eq!(*v.get(0).unwrap(), 10);
+		assert_eq!(*v.get(1).unwrap(), 20);
+		assert!(v.get(2).is_none());
+
+		let all: Vec<i32> = v.iter().map(|x| *x).collect();
+		assert_eq!(all, vec![10, 20]);
+	}
+
+	#[test]
+	fn iter_survives_push_after_collecting() {
+		// `iter` must collect eagerly: pushing enough elements to reallocate the backing
+		// `Vec` after calling `iter()` must not invalidate the references it already handed
+		// out, unlike an iterator that kept borrowing the `Vec` itself.
+		let mut v = VecOwner::new();
+		let mut v = v.child();
+		v.push(Box::new(1));
+		v.push(Box::new(2));
+
+		let collected = v.iter();
+		for i in 0..10_000 {
+			v.push(Box::new(i));
+		}
+
+		assert_eq!(collected.map(|x| *x).collect::<Vec<_>>(), vec![1, 2]);
+	}
+
+	#[test]
+	fn get_and_iter_map() {
+		let mut map = HashMapOwner::new();
+		let mut map = map.child();
+		map.insert("10", Box::new(10));
+		map.insert("20", Box::new(20));
+
+		assert_eq!(*map.get("10").unwrap(), 10);
+		assert_eq!(*map.get("20").unwrap(), 20);
+		assert!(map.get("30").is_none());
+
+		let sum: i32 = map.iter().map(|x| *x).sum();
+		assert_eq!(sum, 30);
+	}
+
+	#[test]
+	fn push_rc_arc_string_vec() {
+		let mut rcs = VecOwner::new();
+		let mut rcs = rcs.child();
+		let r1 = rcs.push(Rc::new(10));
+		assert_eq!(*r1, 10);
+
+		let mut arcs = VecOwner::new();
+		let mut arcs = arcs.child();
+		let r2 = arcs.push(Arc::new(20));
+		assert_eq!(*r2, 20);
+
+		let mut strings = VecOwner::new();
+		let mut strings = strings.child();
+		let r3 = strings.push(String::from("hey"));
+		assert_eq!(&*r3, "hey");
+
+		let mut vecs = VecOwner::new();
+		let mut vecs = vecs.child();
+		let r4 = vecs.push(vec![1, 2, 3]);
+		assert_eq!(&*r4, [1, 2, 3]);
 	}
 
 	#[test]
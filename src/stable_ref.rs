@@ -0,0 +1,64 @@
+//! A narrowable reference returned by `push`/`insert`.
+//!
+//! Behaves like a bare `&'a T::Ref` (via [`Deref`]), but can also be narrowed down to a
+//! subfield with [`map`](StableRef::map) while keeping the same owner lifetime.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+/// A reference tied to an owner's lifetime `'a`, narrowable with [`map`](StableRef::map).
+pub struct StableRef<'a, T: ?Sized> {
+	ptr: *const T,
+	_marker: PhantomData<&'a T>,
+}
+
+impl<'a, T: ?Sized> StableRef<'a, T> {
+	/// # Safety
+	/// `ptr` must stay valid to dereference for the entire lifetime `'a`.
+	#[inline]
+	pub(crate) unsafe fn new(ptr: *const T) -> Self {
+		Self { ptr, _marker: PhantomData }
+	}
+
+	/// Narrows the reference to a subfield or computed view, without re-deriving a raw
+	/// pointer, and without shortening the owner lifetime.
+	///
+	/// ```
+	/// use push_and_read::{VecOwner, VecChild};
+	/// let mut v = VecOwner::new();
+	/// let mut v = v.child();
+	/// let pair = v.push(Box::new((1, String::from("hey"))));
+	/// let name = pair.map(|p| &p.1);
+	/// assert_eq!(&*name, "hey");
+	/// ```
+	pub fn map<U: ?Sized, F>(self, f: F) -> StableRef<'a, U>
+	where F: FnOnce(&T) -> &U {
+		// Safety: `self.ptr` is valid for `'a` by construction, and `f` only ever derives
+		// a reference into the data behind it, so the result is valid for the same `'a`
+		let ptr = f(unsafe { &*self.ptr });
+		unsafe { StableRef::new(ptr) }
+	}
+}
+
+impl<'a, T: ?Sized> Deref for StableRef<'a, T> {
+	type Target = T;
+
+	#[inline]
+	fn deref(&self) -> &T {
+		// Safety: see `StableRef::new`
+		unsafe { &*self.ptr }
+	}
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for StableRef<'a, T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fmt::Debug::fmt(&**self, f)
+	}
+}
+
+// Safety: a `StableRef<'a, T>` behaves exactly like a `&'a T`, so it inherits the same
+// auto-trait requirements: sendable to another thread iff `T` can be read from multiple
+// threads at once.
+unsafe impl<'a, T: ?Sized + Sync> Send for StableRef<'a, T> {}
+unsafe impl<'a, T: ?Sized + Sync> Sync for StableRef<'a, T> {}
@@ -0,0 +1,253 @@
+//! A thread-safe, lock-free owner that can be pushed to concurrently.
+//!
+//! [`VecOwner`](crate::VecOwner) requires `&mut self` to push, and [`FrozenVec`](crate::FrozenVec)
+//! is only sound for single-threaded interior mutability (`UnsafeCell` is `!Sync`). `SyncVecOwner`
+//! fills the gap: wrap it in an `Arc` and push from any number of threads while references handed
+//! out earlier stay valid.
+//!
+//! A plain `Vec` can't be used here because growing it reallocates and moves every element, which
+//! would invalidate references other threads are holding. Instead `SyncVecOwner` keeps a singly
+//! linked list of fixed-capacity chunks: once a chunk is allocated its elements never move again,
+//! only new chunks get linked on. Each chunk hands out slots via an `AtomicUsize` cursor claimed
+//! with `fetch_add`, so two threads can never write the same slot.
+
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+use crate::{StableRef, StaticType};
+
+const CHUNK_LEN: usize = 32;
+
+struct Chunk<T> {
+	slots: [UnsafeCell<MaybeUninit<T>>; CHUNK_LEN],
+	// claims slots via fetch_add; may run past CHUNK_LEN once the chunk is full, those
+	// claims are just discarded and retried in the next chunk
+	cursor: AtomicUsize,
+	next: AtomicPtr<Chunk<T>>,
+}
+
+// Safety: a slot is only ever written by the single thread that claimed it through
+// `cursor.fetch_add`, and never written again afterwards, so sharing `&Chunk<T>` across
+// threads is sound as long as `T` itself may be shared (`Send + Sync`).
+unsafe impl<T: Send + Sync> Sync for Chunk<T> {}
+
+impl<T> Chunk<T> {
+	fn new() -> Box<Self> {
+		Box::new(Self {
+			// Safety: an array of `UnsafeCell<MaybeUninit<T>>` needs no initialization,
+			// `MaybeUninit` exists exactly to allow this
+			slots: unsafe { MaybeUninit::uninit().assume_init() },
+			cursor: AtomicUsize::new(0),
+			next: AtomicPtr::new(ptr::null_mut()),
+		})
+	}
+}
+
+impl<T> Drop for Chunk<T> {
+	fn drop(&mut self) {
+		// every index below `cursor` (capped at CHUNK_LEN) was claimed by exactly one
+		// `push` call, which always writes its slot before returning, so all of them
+		// are initialized
+		let len = (*self.cursor.get_mut()).min(CHUNK_LEN);
+		for slot in &mut self.slots[..len] {
+			unsafe { slot.get_mut().assume_init_drop() };
+		}
+
+		// free the rest of the chain iteratively: recursing into `Chunk::drop` for every
+		// subsequent chunk (via a plain `drop(Box::from_raw(next))`) overflows the stack
+		// once an owner has accumulated enough chunks
+		let mut next = *self.next.get_mut();
+		while !next.is_null() {
+			// Safety: `next` was obtained from `Box::into_raw` and is uniquely owned by
+			// the chain starting at `self`
+			let mut chunk = unsafe { Box::from_raw(next) };
+
+			let len = (*chunk.cursor.get_mut()).min(CHUNK_LEN);
+			for slot in &mut chunk.slots[..len] {
+				unsafe { slot.get_mut().assume_init_drop() };
+			}
+
+			next = *chunk.next.get_mut();
+			// already handled above, so `chunk`'s own `Drop` (run when it goes out of
+			// scope at the end of this iteration) has nothing left to do
+			*chunk.cursor.get_mut() = 0;
+			*chunk.next.get_mut() = ptr::null_mut();
+		}
+	}
+}
+
+
+/// A thread-safe append-only owner, analogous to [`VecOwner`](crate::VecOwner) but pushable
+/// through a shared reference from multiple threads at once.
+///
+/// # Example
+/// ```
+/// use std::sync::Arc;
+/// use push_and_read::SyncVecOwner;
+///
+/// let owner = Arc::new(SyncVecOwner::new());
+/// let v1 = owner.push(Box::new(10));
+///
+/// let owner2 = owner.clone();
+/// std::thread::spawn(move || {
+///     owner2.push(Box::new(20));
+/// }).join().unwrap();
+///
+/// assert_eq!(*v1, 10);
+/// ```
+pub struct SyncVecOwner<T> {
+	// never read directly, but owns the whole chunk chain so it gets freed on drop
+	#[allow(dead_code)]
+	head: Box<Chunk<T>>,
+	tail: AtomicPtr<Chunk<T>>,
+}
+
+impl<T> SyncVecOwner<T>
+where T: StaticType + Send + Sync {
+
+	/// Create a new empty `SyncVecOwner`.
+	pub fn new() -> Self {
+		let mut head = Chunk::new();
+		let head_ptr: *mut Chunk<T> = &mut *head;
+		Self { head, tail: AtomicPtr::new(head_ptr) }
+	}
+
+	/// Pushes a value and returns a reference to it, valid for as long as `self` (or the `Arc`
+	/// wrapping it) lives.
+	pub fn push(&self, v: T) -> StableRef<'_, T::Ref> {
+		// compute the stable pointer before the value moves into a chunk slot
+		let ptr = v.ref_ptr();
+		let mut value = Some(v);
+
+		loop {
+			let chunk_ptr = self.tail.load(Ordering::Acquire);
+			// Safety: chunk_ptr always points at a live chunk owned by `self.head`'s chain
+			let chunk = unsafe { &*chunk_ptr };
+
+			let idx = chunk.cursor.fetch_add(1, Ordering::AcqRel);
+			if idx < CHUNK_LEN {
+				let slot = &chunk.slots[idx];
+				// Safety: fetch_add handed `idx` to exactly this call, so no other
+				// thread ever writes or reads this slot concurrently
+				unsafe { (*slot.get()).write(value.take().unwrap()) };
+
+				// Safety: ptr was derived from the value just written in place, which
+				// never moves again for the lifetime of `self`
+				return unsafe { StableRef::new(ptr) };
+			}
+
+			// chunk is full (or another thread raced us to the same conclusion); make
+			// sure a successor chunk exists and retry there
+			self.grow(chunk, chunk_ptr);
+		}
+	}
+
+	fn grow(&self, chunk: &Chunk<T>, chunk_ptr: *mut Chunk<T>) {
+		let next = chunk.next.load(Ordering::Acquire);
+		let next = if next.is_null() {
+			let new_chunk = Box::into_raw(Chunk::new());
+			match chunk.next.compare_exchange(
+				ptr::null_mut(),
+				new_chunk,
+				Ordering::AcqRel,
+				Ordering::Acquire,
+			) {
+				Ok(_) => new_chunk,
+				Err(existing) => {
+					// someone else linked a chunk first; drop our unused one
+					unsafe { drop(Box::from_raw(new_chunk)) };
+					existing
+				}
+			}
+		} else {
+			next
+		};
+
+		// best effort: advance tail so the next load skips the full chunk; if another
+		// thread already did this, that's fine, we just retry the loop either way
+		let _ = self.tail.compare_exchange(
+			chunk_ptr,
+			next,
+			Ordering::AcqRel,
+			Ordering::Acquire,
+		);
+	}
+}
+
+impl<T> Default for SyncVecOwner<T>
+where T: StaticType + Send + Sync {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+
+	use super::*;
+	use std::sync::Arc;
+
+	fn is_send<T: Send>() {}
+	fn is_sync<T: Sync>() {}
+
+	#[test]
+	fn test_auto_traits() {
+		type Basic = Box<usize>;
+		is_send::<SyncVecOwner<Basic>>();
+		is_sync::<SyncVecOwner<Basic>>();
+	}
+
+	#[test]
+	fn push_from_single_thread() {
+		let owner = SyncVecOwner::new();
+		let v1 = owner.push(Box::new(10));
+		let v2 = owner.push(Box::new(20));
+		assert_eq!(*v1, 10);
+		assert_eq!(*v2, 20);
+	}
+
+	#[test]
+	fn push_across_chunks() {
+		let owner = SyncVecOwner::new();
+		let refs: Vec<_> = (0..100).map(|i| owner.push(Box::new(i))).collect();
+		for (i, r) in refs.into_iter().enumerate() {
+			assert_eq!(*r, i);
+		}
+	}
+
+	#[test]
+	fn drop_many_chunks_without_overflowing_the_stack() {
+		// dropping used to recurse into `Chunk::drop` once per chunk; with CHUNK_LEN
+		// elements per chunk, a couple million pushes built a chain deep enough to blow
+		// the stack on drop
+		let owner = SyncVecOwner::new();
+		for i in 0..2_000_000 {
+			owner.push(Box::new(i));
+		}
+		drop(owner);
+	}
+
+	#[test]
+	fn push_from_multiple_threads() {
+		let owner = Arc::new(SyncVecOwner::new());
+		let v1 = owner.push(Box::new(-1));
+
+		let handles: Vec<_> = (0..8).map(|i| {
+			let owner = owner.clone();
+			std::thread::spawn(move || {
+				for j in 0..50 {
+					owner.push(Box::new(i * 50 + j));
+				}
+			})
+		}).collect();
+
+		for handle in handles {
+			handle.join().unwrap();
+		}
+
+		assert_eq!(*v1, -1);
+	}
+}